@@ -0,0 +1,260 @@
+// Resolves a parsed `Module` into a `Configuration`: looks up each build's
+// rule by name and expands every `$variable` (including the `$in`/`$out`
+// builtins) now that each build's own scope is known.
+
+use crate::ir::{Build, BuildStatement, Configuration, Module, Rule, RuleStatement};
+use std::{collections::HashMap, error, fmt, sync::Arc};
+
+// Ninja's built-in no-op rule: a build that just aggregates outputs under
+// one name, with no command to run.
+const PHONY_RULE: &str = "phony";
+
+#[derive(Debug)]
+pub enum CompileError {
+    UnknownRule(String),
+    MissingCommand(String),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownRule(name) => write!(formatter, "unknown rule: {name}"),
+            Self::MissingCommand(output) => {
+                write!(formatter, "rule used by build of {output} has no command")
+            }
+        }
+    }
+}
+
+impl error::Error for CompileError {}
+
+pub fn compile(module: &Module) -> Result<Configuration, CompileError> {
+    compile_module(module)
+}
+
+// Dynamic modules resolve through the exact same rule lookup and variable
+// expansion as the top-level build file.
+pub fn compile_dynamic(module: &Module) -> Result<Configuration, CompileError> {
+    compile_module(module)
+}
+
+fn compile_module(module: &Module) -> Result<Configuration, CompileError> {
+    let mut outputs = HashMap::new();
+
+    for statement in &module.builds {
+        let build = Arc::new(compile_build(module, statement)?);
+
+        for output in build.outputs().iter().chain(build.implicit_outputs()) {
+            outputs.insert(output.clone(), build.clone());
+        }
+    }
+
+    Ok(Configuration::new(
+        outputs,
+        module.defaults.clone(),
+        module.pools.clone(),
+    ))
+}
+
+fn compile_build(module: &Module, statement: &BuildStatement) -> Result<Build, CompileError> {
+    let scope_in = statement.inputs.join(" ");
+    let scope_out = statement.outputs.join(" ");
+    let empty = HashMap::new();
+
+    let rule_statement = if statement.rule == PHONY_RULE {
+        None
+    } else {
+        Some(
+            module
+                .rules
+                .get(&statement.rule)
+                .ok_or_else(|| CompileError::UnknownRule(statement.rule.clone()))?,
+        )
+    };
+
+    let rule_bindings = rule_statement
+        .map(|statement| &statement.bindings)
+        .unwrap_or(&empty);
+
+    let get = |key: &str| {
+        resolve(
+            key,
+            &statement.bindings,
+            rule_bindings,
+            &module.bindings,
+            &scope_in,
+            &scope_out,
+        )
+    };
+    // The rule's own `pool = name`, ignoring the build's bindings -- a
+    // build's override is exposed separately through `Build::pool`, so
+    // `Rule::pool` must not also pick it up via `resolve`'s usual fallback.
+    let rule_pool = || {
+        resolve(
+            "pool",
+            &empty,
+            rule_bindings,
+            &module.bindings,
+            &scope_in,
+            &scope_out,
+        )
+    };
+
+    let rule = rule_statement
+        .map(|_| {
+            Ok(Rule::new(
+                get("command").ok_or_else(|| CompileError::MissingCommand(scope_out.clone()))?,
+                get("description"),
+                rule_pool(),
+            ))
+        })
+        .transpose()?;
+
+    Ok(Build::new(
+        statement.outputs.clone(),
+        statement.implicit_outputs.clone(),
+        statement
+            .inputs
+            .iter()
+            .chain(&statement.implicit_inputs)
+            .cloned()
+            .collect(),
+        statement.order_only_inputs.clone(),
+        rule,
+        // A build's own `pool = name` is distinct from its rule's: `resolve`
+        // would otherwise happily fall back to the rule's pool binding here
+        // too, so look it up directly in just the build's own bindings.
+        statement.bindings.get("pool").cloned(),
+        get("dyndep"),
+        get("depfile"),
+    ))
+}
+
+// Looks up a binding in the usual ninja scope order -- the build's own
+// overrides, then its rule's, then the file's top-level variables -- and
+// expands any `$variable` reference the resolved value contains.
+fn resolve(
+    key: &str,
+    build_bindings: &HashMap<String, String>,
+    rule_bindings: &HashMap<String, String>,
+    global_bindings: &HashMap<String, String>,
+    scope_in: &str,
+    scope_out: &str,
+) -> Option<String> {
+    build_bindings
+        .get(key)
+        .or_else(|| rule_bindings.get(key))
+        .or_else(|| global_bindings.get(key))
+        .map(|value| expand(value, scope_in, scope_out, global_bindings))
+}
+
+// Expands `$in`/`$out`, `$$` (a literal `$`), `$ ` (a literal space),
+// `${name}`/`$name` (a top-level variable), leaving anything else as-is.
+fn expand(value: &str, scope_in: &str, scope_out: &str, global_bindings: &HashMap<String, String>) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut characters = value.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        if character != '$' {
+            expanded.push(character);
+            continue;
+        }
+
+        match characters.peek() {
+            Some('$') => {
+                characters.next();
+                expanded.push('$');
+            }
+            Some(' ') => {
+                characters.next();
+                expanded.push(' ');
+            }
+            Some('{') => {
+                characters.next();
+
+                let name = characters.by_ref().take_while(|&c| c != '}').collect::<String>();
+
+                expanded.push_str(&expand_variable(&name, scope_in, scope_out, global_bindings));
+            }
+            Some(character) if character.is_alphanumeric() || *character == '_' => {
+                let mut name = String::new();
+
+                while matches!(characters.peek(), Some(c) if c.is_alphanumeric() || *c == '_') {
+                    name.push(characters.next().expect("peeked character"));
+                }
+
+                expanded.push_str(&expand_variable(&name, scope_in, scope_out, global_bindings));
+            }
+            _ => expanded.push('$'),
+        }
+    }
+
+    expanded
+}
+
+fn expand_variable(
+    name: &str,
+    scope_in: &str,
+    scope_out: &str,
+    global_bindings: &HashMap<String, String>,
+) -> String {
+    match name {
+        "in" => scope_in.into(),
+        "out" => scope_out.into(),
+        name => global_bindings.get(name).cloned().unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn resolves_a_rule_and_expands_in_and_out() {
+        let module = parse("rule cc\n  command = cc -c $in -o $out\n\nbuild foo.o: cc foo.c\n").unwrap();
+        let configuration = compile(&module).unwrap();
+        let build = &configuration.outputs()["foo.o"];
+
+        assert_eq!(build.rule().unwrap().command(), "cc -c foo.c -o foo.o");
+    }
+
+    #[test]
+    fn treats_phony_as_a_build_with_no_rule() {
+        let module = parse("build alias: phony foo.o\n").unwrap();
+        let configuration = compile(&module).unwrap();
+
+        assert!(configuration.outputs()["alias"].rule().is_none());
+    }
+
+    #[test]
+    fn rejects_a_build_referencing_an_unknown_rule() {
+        let module = parse("build out: missing in\n").unwrap();
+
+        assert!(matches!(compile(&module), Err(CompileError::UnknownRule(rule)) if rule == "missing"));
+    }
+
+    #[test]
+    fn resolves_pool_depths_and_a_build_level_pool_override() {
+        let module = parse(
+            "pool link_pool\n  depth = 2\n\nrule link\n  command = ld $in -o $out\n  pool = link_pool\n\nbuild out: link in\n  pool = console\n",
+        )
+        .unwrap();
+        let configuration = compile(&module).unwrap();
+        let build = &configuration.outputs()["out"];
+
+        assert_eq!(configuration.pools().get("link_pool"), Some(&2));
+        assert_eq!(build.pool(), Some("console"));
+        assert_eq!(build.rule().unwrap().pool(), Some("link_pool"));
+    }
+
+    #[test]
+    fn resolves_a_build_level_depfile_override() {
+        let module =
+            parse("rule cc\n  command = cc $in\n\nbuild foo.o: cc foo.c\n  depfile = foo.o.d\n")
+                .unwrap();
+        let configuration = compile(&module).unwrap();
+
+        assert_eq!(configuration.outputs()["foo.o"].depfile(), Some("foo.o.d"));
+    }
+}
@@ -0,0 +1,98 @@
+// Every fallible outcome the program can produce -- from the parser/IR
+// layer, the on-disk build database, or a spawned command -- unified behind
+// one error type so `run::run` has a single `Result` to propagate.
+
+use crate::{compile::CompileError, ir::Build, parse::ParseError, validation::ValidationError};
+use std::{
+    error, fmt, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Debug)]
+pub enum InfrastructureError {
+    Io(io::Error),
+    Path { path: PathBuf, source: io::Error },
+    Parse(ParseError),
+    Compile(CompileError),
+    Validation(ValidationError),
+    UnknownPool(String),
+    DuplicateOutput(String),
+    DefaultOutputNotFound(String),
+    DynamicDependencyNotFound(Arc<Build>),
+    CommandExit(String, Option<i32>),
+    Interrupted,
+}
+
+impl InfrastructureError {
+    pub fn with_path(source: io::Error, path: impl AsRef<Path>) -> Self {
+        Self::Path {
+            path: path.as_ref().into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for InfrastructureError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(formatter, "{error}"),
+            Self::Path { path, source } => write!(formatter, "{}: {source}", path.display()),
+            Self::Parse(error) => write!(formatter, "{error}"),
+            Self::Compile(error) => write!(formatter, "{error}"),
+            Self::Validation(error) => write!(formatter, "{error}"),
+            Self::UnknownPool(name) => write!(formatter, "unknown pool: {name}"),
+            Self::DuplicateOutput(output) => {
+                write!(formatter, "output built two different ways: {output}")
+            }
+            Self::DefaultOutputNotFound(output) => {
+                write!(formatter, "default output not found: {output}")
+            }
+            Self::DynamicDependencyNotFound(build) => write!(
+                formatter,
+                "dynamic dependency not found for build of {}",
+                build.outputs().join(" ")
+            ),
+            Self::CommandExit(command, code) => match code {
+                Some(code) => write!(formatter, "command `{command}` exited with status {code}"),
+                None => write!(formatter, "command `{command}` was terminated by a signal"),
+            },
+            Self::Interrupted => write!(formatter, "build interrupted"),
+        }
+    }
+}
+
+impl error::Error for InfrastructureError {}
+
+impl From<io::Error> for InfrastructureError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<tokio::sync::AcquireError> for InfrastructureError {
+    fn from(_error: tokio::sync::AcquireError) -> Self {
+        // The only way a pool's semaphore ever closes is if it is dropped,
+        // and every pool semaphore lives in the `Context` for as long as the
+        // build does, so this is unreachable in practice.
+        Self::Io(io::Error::other("pool semaphore closed"))
+    }
+}
+
+impl From<ParseError> for InfrastructureError {
+    fn from(error: ParseError) -> Self {
+        Self::Parse(error)
+    }
+}
+
+impl From<CompileError> for InfrastructureError {
+    fn from(error: CompileError) -> Self {
+        Self::Compile(error)
+    }
+}
+
+impl From<ValidationError> for InfrastructureError {
+    fn from(error: ValidationError) -> Self {
+        Self::Validation(error)
+    }
+}
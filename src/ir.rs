@@ -0,0 +1,243 @@
+// The resolved build graph `compile` produces from a parsed `Module`, plus
+// `Module` itself -- the raw syntax tree the parser hands to `compile`
+// before anything is looked up or `$variable`-expanded.
+
+use crate::run::path::canonicalize;
+use std::{collections::HashMap, sync::Arc};
+
+// A `rule NAME` block's bindings, exactly as written (`command = cc $in -o
+// $out`), unexpanded. `compile` resolves these once a build's `$in`/`$out`
+// scope is known.
+pub(crate) struct RuleStatement {
+    pub(crate) bindings: HashMap<String, String>,
+}
+
+impl RuleStatement {
+    pub fn new(bindings: HashMap<String, String>) -> Self {
+        Self { bindings }
+    }
+}
+
+// A `build OUT: RULE IN` statement together with its own variable overrides.
+pub(crate) struct BuildStatement {
+    pub(crate) outputs: Vec<String>,
+    pub(crate) implicit_outputs: Vec<String>,
+    pub(crate) rule: String,
+    pub(crate) inputs: Vec<String>,
+    pub(crate) implicit_inputs: Vec<String>,
+    pub(crate) order_only_inputs: Vec<String>,
+    pub(crate) bindings: HashMap<String, String>,
+}
+
+impl BuildStatement {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        outputs: Vec<String>,
+        implicit_outputs: Vec<String>,
+        rule: String,
+        inputs: Vec<String>,
+        implicit_inputs: Vec<String>,
+        order_only_inputs: Vec<String>,
+        bindings: HashMap<String, String>,
+    ) -> Self {
+        Self {
+            outputs,
+            implicit_outputs,
+            rule,
+            inputs,
+            implicit_inputs,
+            order_only_inputs,
+            bindings,
+        }
+    }
+}
+
+// The raw syntax tree produced by the parser: top-level variable bindings,
+// `rule`/`pool` blocks keyed by name, and `build`/`default` statements in
+// file order.
+pub struct Module {
+    pub(crate) bindings: HashMap<String, String>,
+    pub(crate) rules: HashMap<String, RuleStatement>,
+    pub(crate) pools: HashMap<String, usize>,
+    pub(crate) builds: Vec<BuildStatement>,
+    pub(crate) defaults: Vec<String>,
+}
+
+impl Module {
+    pub fn new(
+        bindings: HashMap<String, String>,
+        rules: HashMap<String, RuleStatement>,
+        pools: HashMap<String, usize>,
+        builds: Vec<BuildStatement>,
+        defaults: Vec<String>,
+    ) -> Self {
+        Self {
+            bindings,
+            rules,
+            pools,
+            builds,
+            defaults,
+        }
+    }
+}
+
+// A rule's fully resolved, already-`$variable`-expanded command line.
+pub struct Rule {
+    command: String,
+    description: Option<String>,
+    pool: Option<String>,
+}
+
+impl Rule {
+    pub fn new(command: String, description: Option<String>, pool: Option<String>) -> Self {
+        Self {
+            command,
+            description,
+            pool,
+        }
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn pool(&self) -> Option<&str> {
+        self.pool.as_deref()
+    }
+}
+
+// One edge of the build graph: a set of outputs produced from a set of
+// inputs, optionally by running a `Rule`.
+pub struct Build {
+    id: String,
+    outputs: Vec<String>,
+    implicit_outputs: Vec<String>,
+    inputs: Vec<String>,
+    order_only_inputs: Vec<String>,
+    rule: Option<Rule>,
+    pool: Option<String>,
+    dynamic_module: Option<String>,
+    depfile: Option<String>,
+}
+
+impl Build {
+    // Canonicalizes every path string at construction, so a `Build`'s own
+    // fields already agree on one spelling per file (`./foo.o`, `foo.o`)
+    // before `run`'s `canonicalize_outputs` ever re-keys the outputs map
+    // that holds it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        outputs: Vec<String>,
+        implicit_outputs: Vec<String>,
+        inputs: Vec<String>,
+        order_only_inputs: Vec<String>,
+        rule: Option<Rule>,
+        pool: Option<String>,
+        dynamic_module: Option<String>,
+        depfile: Option<String>,
+    ) -> Self {
+        let outputs = canonicalize_all(&outputs);
+        let implicit_outputs = canonicalize_all(&implicit_outputs);
+        let inputs = canonicalize_all(&inputs);
+        let order_only_inputs = canonicalize_all(&order_only_inputs);
+        let id = outputs.join(" ");
+
+        Self {
+            id,
+            outputs,
+            implicit_outputs,
+            inputs,
+            order_only_inputs,
+            rule,
+            pool,
+            dynamic_module: dynamic_module.map(|path| canonicalize(&path)),
+            depfile: depfile.map(|path| canonicalize(&path)),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn outputs(&self) -> &[String] {
+        &self.outputs
+    }
+
+    pub fn implicit_outputs(&self) -> &[String] {
+        &self.implicit_outputs
+    }
+
+    pub fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    pub fn order_only_inputs(&self) -> &[String] {
+        &self.order_only_inputs
+    }
+
+    pub fn rule(&self) -> Option<&Rule> {
+        self.rule.as_ref()
+    }
+
+    // The pool this build declared directly (`pool = name`), independent of
+    // its rule's own pool -- `run::pool_name` falls back to the rule's when
+    // this is `None`.
+    pub fn pool(&self) -> Option<&str> {
+        self.pool.as_deref()
+    }
+
+    pub fn dynamic_module(&self) -> Option<&str> {
+        self.dynamic_module.as_deref()
+    }
+
+    // The depfile this build's rule asked the compiler to emit (`depfile =
+    // $out.d`), already resolved to a concrete path by `compile`.
+    pub fn depfile(&self) -> Option<&str> {
+        self.depfile.as_deref()
+    }
+}
+
+fn canonicalize_all(paths: &[String]) -> Vec<String> {
+    paths.iter().map(|path| canonicalize(path)).collect()
+}
+
+// The fully resolved build graph `run` drives: every output mapped to the
+// `Build` that produces it, and the outputs to build when none are named on
+// the command line.
+pub struct Configuration {
+    outputs: HashMap<String, Arc<Build>>,
+    default_outputs: Vec<String>,
+    pools: HashMap<String, usize>,
+}
+
+impl Configuration {
+    pub fn new(
+        outputs: HashMap<String, Arc<Build>>,
+        default_outputs: Vec<String>,
+        pools: HashMap<String, usize>,
+    ) -> Self {
+        Self {
+            outputs,
+            default_outputs,
+            pools,
+        }
+    }
+
+    pub fn outputs(&self) -> &HashMap<String, Arc<Build>> {
+        &self.outputs
+    }
+
+    pub fn default_outputs(&self) -> &[String] {
+        &self.default_outputs
+    }
+
+    // Every `pool NAME` block (with its indented `depth = N`) declared in
+    // the build file, by name. `run` adds the default and console pools to this set itself.
+    pub fn pools(&self) -> &HashMap<String, usize> {
+        &self.pools
+    }
+}
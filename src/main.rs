@@ -1,25 +1,153 @@
+mod compile;
+mod error;
 mod ir;
 mod parse;
+mod run;
+mod utilities;
+mod validation;
 
+use compile::compile;
+use error::InfrastructureError;
 use ir::Module;
 use parse::parse;
-use std::error::Error;
+use std::{env, path::PathBuf, process::ExitCode};
 use tokio::{fs::File, io::AsyncReadExt};
 
+struct Arguments {
+    build_directory: PathBuf,
+    job_limit: Option<usize>,
+    debug: bool,
+    profile_path: Option<PathBuf>,
+}
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    read_configuration().await?;
+async fn main() -> ExitCode {
+    let arguments = match parse_arguments(env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(message) => {
+            eprintln!("error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
 
-    Ok(())
+    match run_main(arguments).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
 }
 
-async fn read_configuration() -> Result<Module, Box<dyn Error>> {
-    let mut source = "".into();
+async fn run_main(arguments: Arguments) -> Result<(), InfrastructureError> {
+    // Mirrors ninja's own `-C`: everything after this, including the
+    // `build.ninja` read below and every command a rule runs, resolves
+    // relative to the requested directory rather than wherever we started.
+    env::set_current_dir(&arguments.build_directory)
+        .map_err(|error| InfrastructureError::with_path(error, &arguments.build_directory))?;
+
+    let configuration = compile(&read_configuration().await?)?;
+
+    run::run(
+        configuration,
+        PathBuf::from(".").as_path(),
+        arguments.job_limit,
+        arguments.debug,
+        arguments.profile_path.as_deref(),
+    )
+    .await
+}
+
+async fn read_configuration() -> Result<Module, InfrastructureError> {
+    let mut source = String::new();
 
     File::open("build.ninja")
-        .await?
+        .await
+        .map_err(|error| InfrastructureError::with_path(error, "build.ninja"))?
         .read_to_string(&mut source)
         .await?;
 
     Ok(parse(&source)?)
 }
+
+// A small hand-rolled parser for the handful of flags this binary supports --
+// `-C dir`, `-j n`, `-d`, and `--profile path` -- rather than pulling in an
+// argument-parsing crate for four flags.
+fn parse_arguments(arguments: impl Iterator<Item = String>) -> Result<Arguments, String> {
+    let mut build_directory = PathBuf::from(".");
+    let mut job_limit = None;
+    let mut debug = false;
+    let mut profile_path = None;
+    let mut arguments = arguments.peekable();
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-C" => {
+                build_directory = arguments
+                    .next()
+                    .ok_or("-C requires a directory")?
+                    .into();
+            }
+            "-j" => {
+                let value = arguments.next().ok_or("-j requires a number")?;
+                job_limit = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("-j requires a number, got {value}"))?,
+                );
+            }
+            "-d" => debug = true,
+            "--profile" => {
+                profile_path = Some(
+                    arguments
+                        .next()
+                        .ok_or("--profile requires a path")?
+                        .into(),
+                );
+            }
+            _ => return Err(format!("unrecognized argument: {argument}")),
+        }
+    }
+
+    Ok(Arguments {
+        build_directory,
+        job_limit,
+        debug,
+        profile_path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_to_the_current_directory_with_no_job_limit() {
+        let arguments = parse_arguments(std::iter::empty()).unwrap();
+
+        assert_eq!(arguments.build_directory, PathBuf::from("."));
+        assert_eq!(arguments.job_limit, None);
+        assert!(!arguments.debug);
+        assert_eq!(arguments.profile_path, None);
+    }
+
+    #[test]
+    fn parses_every_flag() {
+        let arguments = parse_arguments(
+            ["-C", "out", "-j", "4", "-d", "--profile", "trace.json"]
+                .iter()
+                .map(|s| s.to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(arguments.build_directory, PathBuf::from("out"));
+        assert_eq!(arguments.job_limit, Some(4));
+        assert!(arguments.debug);
+        assert_eq!(arguments.profile_path, Some(PathBuf::from("trace.json")));
+    }
+
+    #[test]
+    fn rejects_an_unrecognized_flag() {
+        assert!(parse_arguments(["--bogus".to_string()].into_iter()).is_err());
+    }
+}
@@ -0,0 +1,351 @@
+// A hand-rolled, line-oriented parser for the ninja-file subset this
+// project understands: top-level variable bindings, `rule` and `pool`
+// blocks, and `build`/`default` statements. `$variable` expansion happens later, in
+// `compile`, once a build's `$in`/`$out` scope is known.
+
+use crate::ir::{BuildStatement, Module, RuleStatement};
+use std::{collections::HashMap, error, fmt};
+
+#[derive(Debug)]
+pub enum ParseError {
+    UnexpectedIndent(String),
+    MissingRuleName,
+    MissingBuildRule,
+    MissingBindingValue(String),
+    MissingPoolName,
+    InvalidPoolDepth(String, String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnexpectedIndent(line) => {
+                write!(formatter, "indented line outside any block: {line}")
+            }
+            Self::MissingRuleName => write!(formatter, "rule statement is missing a name"),
+            Self::MissingBuildRule => write!(formatter, "build statement is missing a rule name"),
+            Self::MissingBindingValue(line) => {
+                write!(formatter, "binding is missing a `=value`: {line}")
+            }
+            Self::MissingPoolName => write!(formatter, "pool statement is missing a name"),
+            Self::InvalidPoolDepth(name, depth) => {
+                write!(formatter, "pool {name} has a non-numeric depth: {depth}")
+            }
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+pub fn parse(source: &str) -> Result<Module, ParseError> {
+    let mut bindings = HashMap::new();
+    let mut rules = HashMap::new();
+    let mut pools = HashMap::new();
+    let mut builds = vec![];
+    let mut defaults = vec![];
+    let mut block = None;
+
+    for line in join_continuations(source).lines() {
+        let line = strip_comment(line);
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if line.starts_with(' ') || line.starts_with('\t') {
+            let (key, value) = parse_binding(line.trim())?;
+
+            match &mut block {
+                Some(Block::Rule(_, bindings)) | Some(Block::Build(_, bindings)) => {
+                    bindings.insert(key, value);
+                }
+                Some(Block::Pool(name, depth)) => {
+                    if key == "depth" {
+                        *depth = value
+                            .parse()
+                            .map_err(|_| ParseError::InvalidPoolDepth(name.clone(), value))?;
+                    }
+                }
+                None => return Err(ParseError::UnexpectedIndent(line.into())),
+            }
+
+            continue;
+        }
+
+        finish_block(block.take(), &mut rules, &mut pools, &mut builds);
+
+        let trimmed = line.trim();
+        let (directive, rest) = trimmed
+            .split_once(char::is_whitespace)
+            .unwrap_or((trimmed, ""));
+
+        block = match directive {
+            "rule" => {
+                let name = rest.trim();
+
+                if name.is_empty() {
+                    return Err(ParseError::MissingRuleName);
+                }
+
+                Some(Block::Rule(name.into(), HashMap::new()))
+            }
+            "build" => Some(Block::Build(
+                parse_build_header(rest.trim())?,
+                HashMap::new(),
+            )),
+            "pool" => {
+                let name = rest.trim();
+
+                if name.is_empty() {
+                    return Err(ParseError::MissingPoolName);
+                }
+
+                Some(Block::Pool(name.into(), 0))
+            }
+            "default" => {
+                defaults.extend(tokenize(rest.trim()));
+                None
+            }
+            _ => {
+                let (key, value) = parse_binding(trimmed)?;
+                bindings.insert(key, value);
+                None
+            }
+        };
+    }
+
+    finish_block(block.take(), &mut rules, &mut pools, &mut builds);
+
+    Ok(Module::new(bindings, rules, pools, builds, defaults))
+}
+
+// Dynamic-dependency (`dyndep`) files a build discovers at build time share
+// this project's ninja-file syntax, just with a narrower set of statements
+// in practice (typically only `build` edges), so the same parser handles
+// both.
+pub fn parse_dynamic(source: &str) -> Result<Module, ParseError> {
+    parse(source)
+}
+
+enum Block {
+    Rule(String, HashMap<String, String>),
+    Build(BuildHeader, HashMap<String, String>),
+    Pool(String, usize),
+}
+
+struct BuildHeader {
+    outputs: Vec<String>,
+    implicit_outputs: Vec<String>,
+    rule: String,
+    inputs: Vec<String>,
+    implicit_inputs: Vec<String>,
+    order_only_inputs: Vec<String>,
+}
+
+fn finish_block(
+    block: Option<Block>,
+    rules: &mut HashMap<String, RuleStatement>,
+    pools: &mut HashMap<String, usize>,
+    builds: &mut Vec<BuildStatement>,
+) {
+    match block {
+        Some(Block::Rule(name, bindings)) => {
+            rules.insert(name, RuleStatement::new(bindings));
+        }
+        Some(Block::Build(header, bindings)) => {
+            builds.push(BuildStatement::new(
+                header.outputs,
+                header.implicit_outputs,
+                header.rule,
+                header.inputs,
+                header.implicit_inputs,
+                header.order_only_inputs,
+                bindings,
+            ));
+        }
+        Some(Block::Pool(name, depth)) => {
+            pools.insert(name, depth);
+        }
+        None => {}
+    }
+}
+
+// Parses a `build` line's header, e.g.
+// `out1 out2 | impout : rule in1 in2 | impin1 || oo1`, everything after the
+// leading `build` keyword.
+fn parse_build_header(header: &str) -> Result<BuildHeader, ParseError> {
+    // The `:` is usually written right after the last output (`out: rule
+    // in`), so split it off into a token of its own before tokenizing.
+    let header = match header.split_once(':') {
+        Some((outputs, rest)) => format!("{outputs} : {rest}"),
+        None => header.into(),
+    };
+    let mut tokens = tokenize(&header).into_iter().peekable();
+    let outputs = take_until(&mut tokens, &["|", ":"]);
+    let implicit_outputs = if tokens.peek().map(String::as_str) == Some("|") {
+        tokens.next();
+        take_until(&mut tokens, &[":"])
+    } else {
+        vec![]
+    };
+
+    if tokens.peek().map(String::as_str) != Some(":") {
+        return Err(ParseError::MissingBuildRule);
+    }
+
+    tokens.next();
+
+    let rule = tokens.next().ok_or(ParseError::MissingBuildRule)?;
+    let inputs = take_until(&mut tokens, &["|", "||"]);
+    let implicit_inputs = if tokens.peek().map(String::as_str) == Some("|") {
+        tokens.next();
+        take_until(&mut tokens, &["||"])
+    } else {
+        vec![]
+    };
+    let order_only_inputs = if tokens.peek().map(String::as_str) == Some("||") {
+        tokens.next();
+        take_until(&mut tokens, &[])
+    } else {
+        vec![]
+    };
+
+    Ok(BuildHeader {
+        outputs,
+        implicit_outputs,
+        rule,
+        inputs,
+        implicit_inputs,
+        order_only_inputs,
+    })
+}
+
+fn take_until(
+    tokens: &mut std::iter::Peekable<std::vec::IntoIter<String>>,
+    stop: &[&str],
+) -> Vec<String> {
+    let mut taken = vec![];
+
+    while let Some(token) = tokens.peek() {
+        if stop.contains(&token.as_str()) {
+            break;
+        }
+
+        taken.push(tokens.next().expect("peeked token"));
+    }
+
+    taken
+}
+
+// Splits a `name = value` line. The value keeps its raw, unexpanded `$...`
+// escapes -- `compile` is what resolves those, once it knows the scope.
+fn parse_binding(line: &str) -> Result<(String, String), ParseError> {
+    let (key, value) = line
+        .split_once('=')
+        .ok_or_else(|| ParseError::MissingBindingValue(line.into()))?;
+
+    Ok((key.trim().into(), value.trim().into()))
+}
+
+// Ninja's line continuation: a trailing unescaped `$` joins a physical line
+// directly onto the next one.
+fn join_continuations(source: &str) -> String {
+    let source = source.replace("\r\n", "\n");
+    let mut joined = String::with_capacity(source.len());
+    let mut characters = source.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        if character == '$' && characters.peek() == Some(&'$') {
+            // An escaped `$$` is kept as-is for `compile` to expand.
+            joined.push_str("$$");
+            characters.next();
+        } else if character == '$' && characters.peek() == Some(&'\n') {
+            characters.next();
+        } else {
+            joined.push(character);
+        }
+    }
+
+    joined
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find('#') {
+        Some(index) => &line[..index],
+        None => line,
+    }
+}
+
+// Splits on whitespace, treating a `$`-escaped space as a literal space
+// rather than a separator (mirrors `run::depfile::parse`'s escaping, just
+// with ninja's `$` escape character instead of a backslash).
+fn tokenize(source: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut characters = source.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        if character == '$' && characters.peek() == Some(&' ') {
+            token.push(' ');
+            characters.next();
+        } else if character.is_whitespace() {
+            if !token.is_empty() {
+                tokens.push(std::mem::take(&mut token));
+            }
+        } else {
+            token.push(character);
+        }
+    }
+
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_rule_and_a_build_depending_on_it() {
+        let module = parse(
+            "rule cc\n  command = cc -c $in -o $out\n\nbuild foo.o: cc foo.c\n  depfile = foo.o.d\n",
+        )
+        .unwrap();
+
+        assert_eq!(module.builds.len(), 1);
+        assert_eq!(module.builds[0].outputs, vec!["foo.o".to_string()]);
+        assert_eq!(module.builds[0].rule, "cc");
+        assert_eq!(
+            module.builds[0].bindings.get("depfile"),
+            Some(&"foo.o.d".to_string())
+        );
+        assert!(module.rules.contains_key("cc"));
+    }
+
+    #[test]
+    fn parses_implicit_and_order_only_inputs() {
+        let module = parse("build out: cc in | impin || oo\n").unwrap();
+        let build = &module.builds[0];
+
+        assert_eq!(build.inputs, vec!["in".to_string()]);
+        assert_eq!(build.implicit_inputs, vec!["impin".to_string()]);
+        assert_eq!(build.order_only_inputs, vec!["oo".to_string()]);
+    }
+
+    #[test]
+    fn parses_a_pool_block() {
+        let module = parse("pool link_pool\n  depth = 2\n").unwrap();
+
+        assert_eq!(module.pools.get("link_pool"), Some(&2));
+    }
+
+    #[test]
+    fn parses_a_default_statement() {
+        let module = parse("default out1 out2\n").unwrap();
+
+        assert_eq!(module.defaults, vec!["out1".to_string(), "out2".to_string()]);
+    }
+}
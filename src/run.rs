@@ -1,8 +1,19 @@
 mod build_database;
 mod console;
 mod context;
-
-use self::{build_database::BuildDatabase, context::Context};
+mod depfile;
+// `pub(crate)` so the parser/IR layer can also canonicalize a `Build`/`Rule`'s
+// own path strings at construction time, instead of only the outputs map
+// getting re-keyed here.
+pub(crate) mod path;
+mod profile;
+
+use self::{
+    build_database::BuildDatabase,
+    context::Context,
+    path::canonicalize,
+    profile::{format_summary, format_trace, Timing},
+};
 use crate::{
     compile::compile_dynamic,
     error::InfrastructureError,
@@ -14,18 +25,23 @@ use crate::{
 use async_recursion::async_recursion;
 use futures::future::{join_all, try_join_all, FutureExt, Shared};
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, HashMap},
     future::{ready, Future},
     hash::{Hash, Hasher},
     path::Path,
     pin::Pin,
-    sync::Arc,
+    process::{ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::SystemTime,
 };
 use tokio::{
-    fs::metadata,
-    io::{self, AsyncWriteExt},
+    fs::{metadata, read, write},
+    io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader},
     process::Command,
+    signal::ctrl_c,
     spawn,
     sync::Semaphore,
 };
@@ -33,28 +49,121 @@ use tokio::{
 type RawBuildFuture = Pin<Box<dyn Future<Output = Result<(), InfrastructureError>> + Send>>;
 type BuildFuture = Shared<RawBuildFuture>;
 
+// The pool builds fall into when their rule and build specify none.
+const DEFAULT_POOL: &str = "";
+// The reserved pool whose jobs inherit the parent's stdio directly and run
+// exclusively so that nothing else prints while they do.
+const CONSOLE_POOL: &str = "console";
+// How many of the slowest targets to print in the plain-text profile summary.
+const SLOWEST_TARGET_COUNT: usize = 10;
+
+// Tracks which concurrency lane within a pool each running job currently
+// occupies, so the Chrome-trace profile shows genuinely concurrent jobs on
+// distinct tracks instead of collapsing them onto one lane per pool.
+struct PoolSlots {
+    occupied: Vec<AtomicBool>,
+}
+
+impl PoolSlots {
+    fn new(depth: usize) -> Self {
+        Self {
+            occupied: (0..depth).map(|_| AtomicBool::new(false)).collect(),
+        }
+    }
+
+    // Claims the lowest-numbered free lane. Only ever called while holding a
+    // permit from this same pool, so a free lane is always available.
+    fn acquire(&self) -> usize {
+        self.occupied
+            .iter()
+            .position(|slot| {
+                slot.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+            })
+            .unwrap_or(self.occupied.len())
+    }
+
+    fn release(&self, slot: usize) {
+        if let Some(slot) = self.occupied.get(slot) {
+            slot.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+// Releases its pool slot on drop so a job that bails out early (e.g. its
+// command fails to spawn) does not leave the lane permanently occupied.
+struct PoolSlotGuard<'a> {
+    slots: &'a PoolSlots,
+    slot: usize,
+}
+
+impl Drop for PoolSlotGuard<'_> {
+    fn drop(&mut self) {
+        self.slots.release(self.slot);
+    }
+}
+
 pub async fn run(
     configuration: Configuration,
     build_directory: &Path,
     job_limit: Option<usize>,
     debug: bool,
+    profile_path: Option<&Path>,
 ) -> Result<(), InfrastructureError> {
-    let graph = BuildGraph::new(configuration.outputs())?;
+    // Canonicalize every output spelling once, up front, so the build graph,
+    // the build-future table, and every `outputs` lookup downstream agree on
+    // a single key per file regardless of how it was written in build.ninja
+    // (`./foo.o`, `foo.o`, and `a/../foo.o` must all resolve to one node).
+    let outputs = Arc::new(canonicalize_outputs(configuration.outputs())?);
+    let graph = BuildGraph::new(&outputs)?;
+    let mut pool_depths = configuration
+        .pools()
+        .iter()
+        .map(|(name, depth)| (name.clone(), *depth))
+        .collect::<HashMap<_, _>>();
+
+    pool_depths.insert(DEFAULT_POOL.into(), job_limit.unwrap_or_else(num_cpus::get));
+    // The console pool always serializes its jobs, regardless of any `depth`
+    // a user might have declared for it.
+    pool_depths.insert(CONSOLE_POOL.into(), 1);
+
+    // Catch a `pool = <typo>` referencing a name with no `pool NAME` block
+    // here, up front, rather than panicking on a raw HashMap index once the
+    // build is already underway.
+    for build in outputs.values() {
+        let pool = pool_name(build);
+
+        if !pool_depths.contains_key(pool) {
+            return Err(InfrastructureError::UnknownPool(pool.into()));
+        }
+    }
+
+    let pools = pool_depths
+        .iter()
+        .map(|(name, depth)| (name.clone(), Arc::new(Semaphore::new(*depth))))
+        .collect::<HashMap<_, _>>();
+    let pool_slots = Arc::new(
+        pool_depths
+            .into_iter()
+            .map(|(name, depth)| (name, Arc::new(PoolSlots::new(depth))))
+            .collect::<HashMap<_, _>>(),
+    );
+
     let context = Arc::new(Context::new(
         configuration,
         graph,
         BuildDatabase::new(build_directory)?,
-        Semaphore::new(job_limit.unwrap_or_else(num_cpus::get)),
+        pools,
         debug,
     ));
 
     for output in context.configuration().default_outputs() {
         create_build_future(
             &context,
-            context
-                .configuration()
-                .outputs()
-                .get(output)
+            &outputs,
+            &pool_slots,
+            outputs
+                .get(&canonicalize(output))
                 .ok_or_else(|| InfrastructureError::DefaultOutputNotFound(output.into()))?,
         )
         .await?;
@@ -69,15 +178,81 @@ pub async fn run(
         .cloned()
         .collect::<Vec<_>>();
 
-    // Start running build futures actually.
-    join_builds(futures).await?;
+    // Start running build futures actually. On Ctrl-C, stop handing out new
+    // jobs and let the ones already running finish (and persist their
+    // progress) instead of tearing everything down mid-write.
+    let fail_fast = join_builds(futures.clone());
+    tokio::pin!(fail_fast);
+
+    // Captured rather than propagated immediately so that, whether a build
+    // failed outright or was interrupted, the profile below still gets
+    // written before the error reaches the caller.
+    let result = tokio::select! {
+        result = &mut fail_fast => result,
+        _ = ctrl_c() => {
+            context.request_shutdown();
+
+            // `fail_fast` would return as soon as any not-yet-started job
+            // observes the shutdown flag and bails out with `Interrupted`,
+            // abandoning whichever jobs were already mid-command. Wait for
+            // every one of them here instead, so they finish and persist
+            // their hash before we exit.
+            join_all(futures).await;
+
+            Err(InfrastructureError::Interrupted)
+        }
+    };
+
+    if let Some(profile_path) = profile_path {
+        let timings = context.timings().lock().await;
+
+        // Best-effort: a build's real outcome (including a failure already
+        // captured in `result`) must not be replaced by a profile file that
+        // failed to write (bad path, permissions, full disk).
+        match write(profile_path, format_trace(&timings)).await {
+            Ok(()) => println!("{}", format_summary(&timings, SLOWEST_TARGET_COUNT)),
+            Err(error) => eprintln!(
+                "warning: failed to write build profile to {}: {error}",
+                profile_path.display()
+            ),
+        }
+    }
 
-    Ok(())
+    result
+}
+
+// Re-keys an outputs map by canonicalized path so that every lookup against
+// it, however an input or output was spelled in build.ninja, lands on the
+// same build node. Two of a single build's own output/implicit-output
+// spellings canonicalizing to the same key is expected (that's exactly why
+// this exists); two *different* builds colliding on one canonical output is
+// not, and is reported rather than silently letting the later one win.
+fn canonicalize_outputs(
+    outputs: &HashMap<String, Arc<Build>>,
+) -> Result<HashMap<String, Arc<Build>>, InfrastructureError> {
+    let mut canonicalized: HashMap<String, Arc<Build>> = HashMap::new();
+
+    for (output, build) in outputs {
+        let key = canonicalize(output);
+
+        match canonicalized.get(&key) {
+            Some(existing) if !Arc::ptr_eq(existing, build) => {
+                return Err(InfrastructureError::DuplicateOutput(key));
+            }
+            _ => {
+                canonicalized.insert(key, build.clone());
+            }
+        }
+    }
+
+    Ok(canonicalized)
 }
 
 #[async_recursion]
 async fn create_build_future(
     context: &Arc<Context>,
+    outputs: &Arc<HashMap<String, Arc<Build>>>,
+    pool_slots: &Arc<HashMap<String, Arc<PoolSlots>>>,
     build: &Arc<Build>,
 ) -> Result<(), InfrastructureError> {
     // Exclusive lock for atomic addition of a build job.
@@ -87,7 +262,12 @@ async fn create_build_future(
         return Ok(());
     }
 
-    let future: RawBuildFuture = Box::pin(spawn_build_future(context.clone(), build.clone()));
+    let future: RawBuildFuture = Box::pin(spawn_build_future(
+        context.clone(),
+        outputs.clone(),
+        pool_slots.clone(),
+        build.clone(),
+    ));
 
     builds.insert(build.id().into(), future.shared());
 
@@ -96,15 +276,21 @@ async fn create_build_future(
 
 async fn spawn_build_future(
     context: Arc<Context>,
+    outputs: Arc<HashMap<String, Arc<Build>>>,
+    pool_slots: Arc<HashMap<String, Arc<PoolSlots>>>,
     build: Arc<Build>,
 ) -> Result<(), InfrastructureError> {
     spawn(async move {
+        if context.is_shutting_down() {
+            return Err(InfrastructureError::Interrupted);
+        }
+
         let mut futures = vec![];
 
         for input in build.inputs().iter().chain(build.order_only_inputs()) {
             futures.push(
-                if let Some(build) = context.configuration().outputs().get(input) {
-                    create_build_future(&context, build).await?;
+                if let Some(build) = outputs.get(&canonicalize(input)) {
+                    create_build_future(&context, &outputs, &pool_slots, build).await?;
 
                     context.build_futures().read().await[build.id()].clone()
                 } else {
@@ -120,22 +306,36 @@ async fn spawn_build_future(
         join_builds(futures).await?;
 
         // TODO Consider caching dynamic modules.
-        let dynamic_configuration = if let Some(dynamic_module) = build.dynamic_module() {
+        let dynamic_outputs = if let Some(dynamic_module) = build.dynamic_module() {
             let configuration =
                 compile_dynamic(&parse_dynamic(&read_file(&dynamic_module).await?)?)?;
 
             context.build_graph().lock().await.insert(&configuration)?;
 
-            Some(configuration)
+            let outputs = canonicalize_outputs(configuration.outputs())?;
+
+            // A dynamically discovered build can declare a `pool = <typo>`
+            // just like a statically parsed one; catch it here rather than
+            // panicking on a raw HashMap index once `run_rule` looks up that
+            // pool's semaphore and slots.
+            for build in outputs.values() {
+                let pool = pool_name(build);
+
+                if !pool_slots.contains_key(pool) {
+                    return Err(InfrastructureError::UnknownPool(pool.into()));
+                }
+            }
+
+            Some(outputs)
         } else {
             None
         };
 
-        let dynamic_inputs = if let Some(configuration) = &dynamic_configuration {
+        let dynamic_inputs = if let Some(dynamic_outputs) = &dynamic_outputs {
             build
                 .outputs()
                 .iter()
-                .find_map(|output| configuration.outputs().get(output.as_str()))
+                .find_map(|output| dynamic_outputs.get(&canonicalize(output)))
                 .map(|build| build.inputs())
                 .ok_or_else(|| InfrastructureError::DynamicDependencyNotFound(build.clone()))?
         } else {
@@ -145,18 +345,25 @@ async fn spawn_build_future(
         let mut futures = vec![];
 
         for input in dynamic_inputs {
-            let build = &context.configuration().outputs()[input];
+            let build = &outputs[&canonicalize(input)];
 
-            create_build_future(&context, build).await?;
+            create_build_future(&context, &outputs, &pool_slots, build).await?;
 
             futures.push(context.build_futures().read().await[build.id()].clone());
         }
 
         join_builds(futures).await?;
 
-        let hash = hash_build(&build, dynamic_inputs).await?;
+        let discovered_inputs = context.database().discovered_inputs(build.id())?;
+        let hash = hash_build(
+            &context,
+            &build,
+            dynamic_inputs.iter().chain(&discovered_inputs),
+        )
+        .await?;
 
-        if hash == context.database().get(build.id())?
+        if !context.database().was_in_progress(build.id())?
+            && hash == context.database().get(build.id())?
             && try_join_all(
                 build
                     .outputs()
@@ -169,7 +376,49 @@ async fn spawn_build_future(
         {
             return Ok(());
         } else if let Some(rule) = build.rule() {
-            run_rule(&context, rule).await?;
+            // Dependencies can finish after Ctrl-C; do not start a new
+            // command once a shutdown has been requested.
+            if context.is_shutting_down() {
+                return Err(InfrastructureError::Interrupted);
+            }
+
+            // Persisted before running so that, if we are killed mid-command,
+            // the next invocation knows this output cannot be trusted even
+            // though no new hash was ever recorded for it.
+            context.database().mark_in_progress(build.id())?;
+
+            run_rule(&context, &pool_slots, &build, rule).await?;
+
+            // Restat the outputs so that, when their content did not actually
+            // change, builds downstream of them keep seeing the hash they had
+            // before this rule ran and can be skipped.
+            for output in build.outputs().iter().chain(build.implicit_outputs()) {
+                hash_input(&context, output).await?;
+            }
+
+            let discovered_inputs = if let Some(depfile) = build.depfile() {
+                depfile::parse(&read_file(depfile).await?)
+                    .iter()
+                    .map(|path| canonicalize(path))
+                    .collect()
+            } else {
+                vec![]
+            };
+
+            let hash = hash_build(
+                &context,
+                &build,
+                dynamic_inputs.iter().chain(&discovered_inputs),
+            )
+            .await?;
+
+            context
+                .database()
+                .set_discovered_inputs(build.id(), discovered_inputs)?;
+            context.database().set(build.id(), hash)?;
+            context.database().clear_in_progress(build.id())?;
+
+            return Ok(());
         }
 
         context.database().set(build.id(), hash)?;
@@ -179,7 +428,11 @@ async fn spawn_build_future(
     .await?
 }
 
-async fn hash_build(build: &Build, dynamic_inputs: &[String]) -> Result<u64, InfrastructureError> {
+async fn hash_build<'a>(
+    context: &Context,
+    build: &Build,
+    extra_inputs: impl Iterator<Item = &'a String>,
+) -> Result<u64, InfrastructureError> {
     let mut hasher = DefaultHasher::new();
 
     build.rule().map(Rule::command).hash(&mut hasher);
@@ -187,25 +440,47 @@ async fn hash_build(build: &Build, dynamic_inputs: &[String]) -> Result<u64, Inf
         build
             .inputs()
             .iter()
-            .chain(dynamic_inputs)
-            .map(get_timestamp),
+            .chain(extra_inputs)
+            .map(|input| hash_input(context, input)),
     )
     .await
     .into_iter()
-    .collect::<Result<Vec<SystemTime>, _>>()?
+    .collect::<Result<Vec<u64>, _>>()?
     .hash(&mut hasher);
 
     Ok(hasher.finish())
 }
 
-async fn get_timestamp(path: impl AsRef<Path>) -> Result<SystemTime, InfrastructureError> {
+// Hashes an input's content, reusing the cached hash whenever the file's
+// modification time and size have not changed to avoid re-reading its bytes.
+async fn hash_input(context: &Context, path: impl AsRef<Path>) -> Result<u64, InfrastructureError> {
     let path = path.as_ref();
+    let key = canonicalize(&path.to_string_lossy());
+    let metadata = metadata(path)
+        .await
+        .map_err(|error| InfrastructureError::with_path(error, path))?;
+    let modified = metadata
+        .modified()
+        .map_err(|error| InfrastructureError::with_path(error, path))?;
+    let size = metadata.len();
 
-    Ok(metadata(path)
+    if let Some((cached_modified, cached_size, cached_hash)) = context.database().file_stat(&key)?
+    {
+        if cached_modified == modified && cached_size == size {
+            return Ok(cached_hash);
+        }
+    }
+
+    let mut hasher = DefaultHasher::new();
+    read(path)
         .await
         .map_err(|error| InfrastructureError::with_path(error, path))?
-        .modified()
-        .map_err(|error| InfrastructureError::with_path(error, path))?)
+        .hash(&mut hasher);
+    let hash = hasher.finish();
+
+    context.database().set_file_stat(&key, modified, size, hash)?;
+
+    Ok(hash)
 }
 
 async fn join_builds(
@@ -228,39 +503,146 @@ async fn check_file_existence(path: impl AsRef<Path>) -> Result<(), Infrastructu
     Ok(())
 }
 
-async fn run_rule(context: &Context, rule: &Rule) -> Result<(), InfrastructureError> {
-    let permit = context.job_semaphore().acquire().await?;
-    let output = Command::new("sh")
-        .arg("-e")
-        .arg("-c")
-        .arg(rule.command())
-        .output()
-        .await?;
-    drop(permit);
-
-    let mut console = context.console().lock().await;
+fn pool_name(build: &Build) -> &str {
+    build
+        .pool()
+        .or_else(|| build.rule().and_then(Rule::pool))
+        .unwrap_or(DEFAULT_POOL)
+}
 
-    if context.debug() {
-        writeln(console.stderr(), rule.command()).await?;
+// Builds the `sh -e -c <command>` invocation shared by both the console and
+// streamed job paths. On Unix, a streamed job is put into its own process
+// group so the terminal's SIGINT on Ctrl-C reaches only this process, not jobs
+// already spawned -- otherwise `run`'s "let in-flight jobs finish" handling
+// above would race the shell itself getting killed out from under it. A
+// console job stays in the terminal's foreground process group, since a
+// background group cannot read from the terminal; Ctrl-C reaches it too.
+fn new_shell_command(command: &str, own_process_group: bool) -> Command {
+    let mut command = {
+        let mut sh = Command::new("sh");
+        sh.arg("-e").arg("-c").arg(command);
+        sh
+    };
+
+    #[cfg(unix)]
+    if own_process_group {
+        command.process_group(0);
     }
 
-    if let Some(description) = rule.description() {
-        writeln(console.stderr(), description).await?;
-    }
+    command
+}
+
+async fn run_rule(
+    context: &Context,
+    pool_slots: &HashMap<String, Arc<PoolSlots>>,
+    build: &Build,
+    rule: &Rule,
+) -> Result<(), InfrastructureError> {
+    let pool = pool_name(build);
+    let permit = context.pool_semaphore(pool).acquire().await?;
+    let slots = &pool_slots[pool];
+    let slot_guard = PoolSlotGuard {
+        slots,
+        slot: slots.acquire(),
+    };
+
+    let start = SystemTime::now();
+
+    let status = if pool == CONSOLE_POOL {
+        // Interactive jobs talk to the terminal directly. The pool permit
+        // above only keeps other console-pool jobs from overlapping; a job
+        // in any other pool still writes through the console lock in
+        // `run_rule_streamed`, so take that same lock before the debug and
+        // description writes and hold it for as long as this command owns
+        // the inherited stdio, to keep the two from interleaving.
+        let mut console = context.console().lock().await;
+
+        if context.debug() {
+            writeln(console.stderr(), rule.command()).await?;
+        }
 
-    console.stdout().write_all(&output.stdout).await?;
-    console.stderr().write_all(&output.stderr).await?;
+        if let Some(description) = rule.description() {
+            writeln(console.stderr(), description).await?;
+        }
+
+        new_shell_command(rule.command(), false)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()
+            .await?
+    } else {
+        if context.debug() {
+            writeln(context.console().lock().await.stderr(), rule.command()).await?;
+        }
+
+        if let Some(description) = rule.description() {
+            writeln(context.console().lock().await.stderr(), description).await?;
+        }
 
-    if !output.status.success() {
+        run_rule_streamed(context, rule).await?
+    };
+
+    let duration = start.elapsed().unwrap_or_default();
+    let slot = slot_guard.slot;
+
+    // Release the pool slot before the semaphore permit: `PoolSlots::acquire`
+    // assumes a free lane is always available to whoever holds a permit, so
+    // freeing the permit first could let a waiting task acquire it and call
+    // `acquire()` while this lane is still marked occupied.
+    drop(slot_guard);
+    drop(permit);
+
+    context.timings().lock().await.push(Timing {
+        name: build.outputs().join(" "),
+        pool: pool.into(),
+        slot,
+        start,
+        duration,
+    });
+
+    if !status.success() {
         return Err(InfrastructureError::CommandExit(
             rule.command().into(),
-            output.status.code(),
+            status.code(),
         ));
     }
 
     Ok(())
 }
 
+// Forwards a job's stdout/stderr to the console line by line as they arrive
+// instead of buffering the whole output until the job exits.
+async fn run_rule_streamed(
+    context: &Context,
+    rule: &Rule,
+) -> Result<ExitStatus, InfrastructureError> {
+    let mut child = new_shell_command(rule.command(), true)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let mut stdout_lines = BufReader::new(child.stdout.take().expect("piped stdout")).lines();
+    let mut stderr_lines = BufReader::new(child.stderr.take().expect("piped stderr")).lines();
+    let mut stdout_done = false;
+    let mut stderr_done = false;
+
+    while !stdout_done || !stderr_done {
+        tokio::select! {
+            line = stdout_lines.next_line(), if !stdout_done => match line? {
+                Some(line) => writeln(context.console().lock().await.stdout(), line).await?,
+                None => stdout_done = true,
+            },
+            line = stderr_lines.next_line(), if !stderr_done => match line? {
+                Some(line) => writeln(context.console().lock().await.stderr(), line).await?,
+                None => stderr_done = true,
+            },
+        }
+    }
+
+    Ok(child.wait().await?)
+}
+
 async fn writeln(
     writer: &mut (impl AsyncWriteExt + Unpin),
     message: impl AsRef<str>,
@@ -0,0 +1,249 @@
+// Persists each build's last-known content hash, in-progress marker, and
+// depfile-discovered inputs to one file in the build directory, so a build
+// interrupted by Ctrl-C (see `run`'s Ctrl-C branch) can tell on its next
+// invocation which outputs were left mid-command instead of starting over.
+// Also caches a file's content hash against the mtime/size it was computed
+// from, so `hash_input` can skip re-reading a file's bytes when neither has
+// changed.
+
+use crate::error::InfrastructureError;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const STATE_FILE_NAME: &str = ".turtle-build-state";
+// Build ids and discovered-input paths may themselves contain spaces, so
+// the fields of a record (one record per line) are separated by the ASCII
+// unit separator instead of whitespace.
+const FIELD_SEPARATOR: char = '\u{1f}';
+
+struct State {
+    hashes: HashMap<String, u64>,
+    in_progress: HashSet<String>,
+    discovered_inputs: HashMap<String, Vec<String>>,
+    file_stats: HashMap<String, (SystemTime, u64, u64)>,
+}
+
+impl State {
+    fn empty() -> Self {
+        Self {
+            hashes: HashMap::new(),
+            in_progress: HashSet::new(),
+            discovered_inputs: HashMap::new(),
+            file_stats: HashMap::new(),
+        }
+    }
+}
+
+pub struct BuildDatabase {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+impl BuildDatabase {
+    pub fn new(build_directory: &Path) -> Result<Self, InfrastructureError> {
+        let path = build_directory.join(STATE_FILE_NAME);
+        let state = match fs::read_to_string(&path) {
+            Ok(source) => parse(&source),
+            Err(error) if error.kind() == ErrorKind::NotFound => State::empty(),
+            Err(error) => return Err(InfrastructureError::with_path(error, &path)),
+        };
+
+        Ok(Self {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<u64>, InfrastructureError> {
+        Ok(self.lock().hashes.get(id).copied())
+    }
+
+    pub fn set(&self, id: &str, hash: u64) -> Result<(), InfrastructureError> {
+        let mut state = self.lock();
+        state.hashes.insert(id.into(), hash);
+        self.persist(&state)
+    }
+
+    pub fn was_in_progress(&self, id: &str) -> Result<bool, InfrastructureError> {
+        Ok(self.lock().in_progress.contains(id))
+    }
+
+    pub fn mark_in_progress(&self, id: &str) -> Result<(), InfrastructureError> {
+        let mut state = self.lock();
+        state.in_progress.insert(id.into());
+        self.persist(&state)
+    }
+
+    pub fn clear_in_progress(&self, id: &str) -> Result<(), InfrastructureError> {
+        let mut state = self.lock();
+        state.in_progress.remove(id);
+        self.persist(&state)
+    }
+
+    pub fn discovered_inputs(&self, id: &str) -> Result<Vec<String>, InfrastructureError> {
+        Ok(self
+            .lock()
+            .discovered_inputs
+            .get(id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    pub fn set_discovered_inputs(
+        &self,
+        id: &str,
+        inputs: Vec<String>,
+    ) -> Result<(), InfrastructureError> {
+        let mut state = self.lock();
+        state.discovered_inputs.insert(id.into(), inputs);
+        self.persist(&state)
+    }
+
+    pub fn file_stat(
+        &self,
+        key: &str,
+    ) -> Result<Option<(SystemTime, u64, u64)>, InfrastructureError> {
+        Ok(self.lock().file_stats.get(key).copied())
+    }
+
+    pub fn set_file_stat(
+        &self,
+        key: &str,
+        modified: SystemTime,
+        size: u64,
+        hash: u64,
+    ) -> Result<(), InfrastructureError> {
+        let mut state = self.lock();
+        state.file_stats.insert(key.into(), (modified, size, hash));
+        self.persist(&state)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn persist(&self, state: &State) -> Result<(), InfrastructureError> {
+        fs::write(&self.path, format(state))
+            .map_err(|error| InfrastructureError::with_path(error, &self.path))
+    }
+}
+
+fn format(state: &State) -> String {
+    let mut lines = vec![];
+
+    for (id, hash) in &state.hashes {
+        lines.push(format!("H{FIELD_SEPARATOR}{id}{FIELD_SEPARATOR}{hash}"));
+    }
+
+    for id in &state.in_progress {
+        lines.push(format!("P{FIELD_SEPARATOR}{id}"));
+    }
+
+    for (id, inputs) in &state.discovered_inputs {
+        let mut fields = vec!["D".to_string(), id.clone()];
+        fields.extend(inputs.iter().cloned());
+        lines.push(fields.join(&FIELD_SEPARATOR.to_string()));
+    }
+
+    for (key, (modified, size, hash)) in &state.file_stats {
+        let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+        lines.push(format!(
+            "F{FIELD_SEPARATOR}{key}{FIELD_SEPARATOR}{}{FIELD_SEPARATOR}{}{FIELD_SEPARATOR}{size}{FIELD_SEPARATOR}{hash}",
+            since_epoch.as_secs(),
+            since_epoch.subsec_nanos(),
+        ));
+    }
+
+    lines.join("\n")
+}
+
+fn parse(source: &str) -> State {
+    let mut state = State::empty();
+
+    for line in source.lines() {
+        let mut fields = line.split(FIELD_SEPARATOR);
+
+        match fields.next() {
+            Some("H") => {
+                if let (Some(id), Some(Ok(hash))) = (fields.next(), fields.next().map(str::parse)) {
+                    state.hashes.insert(id.into(), hash);
+                }
+            }
+            Some("P") => {
+                if let Some(id) = fields.next() {
+                    state.in_progress.insert(id.into());
+                }
+            }
+            Some("D") => {
+                if let Some(id) = fields.next() {
+                    state
+                        .discovered_inputs
+                        .insert(id.into(), fields.map(String::from).collect());
+                }
+            }
+            Some("F") => {
+                let rest = (fields.next(), fields.next(), fields.next(), fields.next());
+
+                if let (Some(key), Some(secs), Some(nanos), Some(size)) = rest {
+                    if let (Ok(secs), Ok(nanos), Ok(size), Some(Ok(hash))) = (
+                        secs.parse(),
+                        nanos.parse(),
+                        size.parse(),
+                        fields.next().map(str::parse),
+                    ) {
+                        state.file_stats.insert(
+                            key.into(),
+                            (UNIX_EPOCH + Duration::new(secs, nanos), size, hash),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_a_hash_through_format_and_parse() {
+        let mut state = State::empty();
+        state.hashes.insert("out".into(), 42);
+
+        let reparsed = parse(&format(&state));
+
+        assert_eq!(reparsed.hashes.get("out"), Some(&42));
+    }
+
+    #[test]
+    fn round_trips_discovered_inputs_and_a_file_stat() {
+        let mut state = State::empty();
+        state
+            .discovered_inputs
+            .insert("out".into(), vec!["a.h".into(), "b.h".into()]);
+        state
+            .file_stats
+            .insert("a.h".into(), (UNIX_EPOCH + Duration::from_secs(5), 10, 99));
+
+        let reparsed = parse(&format(&state));
+
+        assert_eq!(
+            reparsed.discovered_inputs.get("out"),
+            Some(&vec!["a.h".to_string(), "b.h".to_string()])
+        );
+        assert_eq!(
+            reparsed.file_stats.get("a.h"),
+            Some(&(UNIX_EPOCH + Duration::from_secs(5), 10, 99))
+        );
+    }
+}
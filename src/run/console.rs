@@ -0,0 +1,34 @@
+// Wraps the process's real stdout/stderr behind one handle so concurrent
+// jobs' output can be serialized through a single lock (see `run_rule` and
+// `run_rule_streamed`) instead of each job writing to the terminal
+// independently.
+
+use tokio::io::{stderr, stdout, Stderr, Stdout};
+
+pub struct Console {
+    stdout: Stdout,
+    stderr: Stderr,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self {
+            stdout: stdout(),
+            stderr: stderr(),
+        }
+    }
+
+    pub fn stdout(&mut self) -> &mut Stdout {
+        &mut self.stdout
+    }
+
+    pub fn stderr(&mut self) -> &mut Stderr {
+        &mut self.stderr
+    }
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
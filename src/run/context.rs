@@ -0,0 +1,94 @@
+// Bundles everything a running build shares across jobs: the resolved
+// configuration, the build graph (which dynamic modules extend as they're
+// discovered), the on-disk build database, per-pool semaphores, the
+// in-flight build-future table, the console lock, collected timings, and
+// the Ctrl-C shutdown flag.
+
+use super::{build_database::BuildDatabase, console::Console, profile::Timing, BuildFuture};
+use crate::{ir::Configuration, validation::BuildGraph};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::{Mutex, RwLock, Semaphore};
+
+pub struct Context {
+    configuration: Configuration,
+    build_graph: Mutex<BuildGraph>,
+    database: BuildDatabase,
+    pools: HashMap<String, Arc<Semaphore>>,
+    debug: bool,
+    build_futures: RwLock<HashMap<String, BuildFuture>>,
+    console: Mutex<Console>,
+    timings: Mutex<Vec<Timing>>,
+    shutting_down: AtomicBool,
+}
+
+impl Context {
+    pub fn new(
+        configuration: Configuration,
+        build_graph: BuildGraph,
+        database: BuildDatabase,
+        pools: HashMap<String, Arc<Semaphore>>,
+        debug: bool,
+    ) -> Self {
+        Self {
+            configuration,
+            build_graph: Mutex::new(build_graph),
+            database,
+            pools,
+            debug,
+            build_futures: RwLock::new(HashMap::new()),
+            console: Mutex::new(Console::new()),
+            timings: Mutex::new(vec![]),
+            shutting_down: AtomicBool::new(false),
+        }
+    }
+
+    pub fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    pub fn build_graph(&self) -> &Mutex<BuildGraph> {
+        &self.build_graph
+    }
+
+    pub fn database(&self) -> &BuildDatabase {
+        &self.database
+    }
+
+    // Every pool name indexed here is guaranteed present: `run` validates
+    // every statically-parsed build's pool up front, and `spawn_build_future`
+    // validates a dynamic module's the same way right after it's discovered,
+    // so by the time a job reaches `run_rule` its pool is already known-good.
+    pub fn pool_semaphore(&self, name: &str) -> &Arc<Semaphore> {
+        &self.pools[name]
+    }
+
+    pub fn debug(&self) -> bool {
+        self.debug
+    }
+
+    pub fn build_futures(&self) -> &RwLock<HashMap<String, BuildFuture>> {
+        &self.build_futures
+    }
+
+    pub fn console(&self) -> &Mutex<Console> {
+        &self.console
+    }
+
+    pub fn timings(&self) -> &Mutex<Vec<Timing>> {
+        &self.timings
+    }
+
+    pub fn request_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,63 @@
+// Parses Makefile-style depfiles emitted by compilers via flags such as
+// `gcc -MMD -MF`, e.g. `foo.o: foo.c foo.h \
+//   bar.h`.
+
+pub fn parse(source: &str) -> Vec<String> {
+    let joined = source.replace("\\\r\n", " ").replace("\\\n", " ");
+
+    joined
+        .split_once(':')
+        .map(|(_, prerequisites)| tokenize(prerequisites))
+        .unwrap_or_default()
+}
+
+fn tokenize(source: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut characters = source.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        if character == '\\' && characters.peek() == Some(&' ') {
+            token.push(' ');
+            characters.next();
+        } else if character.is_whitespace() {
+            if !token.is_empty() {
+                tokens.push(std::mem::take(&mut token));
+            }
+        } else {
+            token.push(character);
+        }
+    }
+
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_line_continuation() {
+        assert_eq!(
+            parse("foo.o: foo.c \\\n  foo.h bar.h\n"),
+            vec!["foo.c", "foo.h", "bar.h"]
+        );
+    }
+
+    #[test]
+    fn parses_escaped_space_in_prerequisite() {
+        assert_eq!(
+            parse("foo.o: foo.c path\\ with\\ spaces.h\n"),
+            vec!["foo.c", "path with spaces.h"]
+        );
+    }
+
+    #[test]
+    fn returns_no_prerequisites_without_a_colon() {
+        assert_eq!(parse("foo.c foo.h\n"), Vec::<String>::new());
+    }
+}
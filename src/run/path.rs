@@ -0,0 +1,67 @@
+// Lexically normalizes a path string so that equivalent spellings (`./foo`,
+// `foo`, `a/../foo`) resolve to the same cache and build-graph key without
+// touching the filesystem.
+
+pub fn canonicalize(path: &str) -> String {
+    let absolute = path.starts_with('/');
+    let mut components = vec![];
+
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if matches!(components.last(), Some(&last) if last != "..") {
+                    components.pop();
+                } else if !absolute {
+                    components.push("..");
+                }
+            }
+            component => components.push(component),
+        }
+    }
+
+    let joined = components.join("/");
+
+    if absolute {
+        format!("/{joined}")
+    } else if joined.is_empty() {
+        ".".into()
+    } else {
+        joined
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_leading_current_directory() {
+        assert_eq!(canonicalize("./foo"), "foo");
+    }
+
+    #[test]
+    fn resolves_parent_component_against_a_prior_one() {
+        assert_eq!(canonicalize("a/../b"), "b");
+    }
+
+    #[test]
+    fn keeps_parent_components_that_escape_above_the_start() {
+        assert_eq!(canonicalize("../escape-above-root"), "../escape-above-root");
+    }
+
+    #[test]
+    fn clamps_parent_components_at_the_absolute_root() {
+        assert_eq!(canonicalize("/../foo"), "/foo");
+    }
+
+    #[test]
+    fn collapses_redundant_separators() {
+        assert_eq!(canonicalize("foo//bar/"), "foo/bar");
+    }
+
+    #[test]
+    fn treats_the_empty_path_as_current_directory() {
+        assert_eq!(canonicalize(""), ".");
+    }
+}
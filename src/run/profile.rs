@@ -0,0 +1,133 @@
+// Serializes per-rule timings into Chrome's trace-event JSON format so a
+// build's profile can be loaded directly in chrome://tracing or Perfetto.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub struct Timing {
+    pub name: String,
+    pub pool: String,
+    // The concurrency lane this run actually occupied within its pool, so
+    // that genuinely concurrent jobs land on distinct Chrome-trace tracks.
+    pub slot: usize,
+    pub start: SystemTime,
+    pub duration: Duration,
+}
+
+pub fn format_trace(timings: &[Timing]) -> String {
+    let events = timings
+        .iter()
+        .map(|timing| {
+            format!(
+                r#"{{"name":{},"ph":"X","ts":{},"dur":{},"pid":{},"tid":{},"cat":"build"}}"#,
+                json_string(&timing.name),
+                timing
+                    .start
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_micros(),
+                timing.duration.as_micros(),
+                pool_pid(&timing.pool),
+                timing.slot,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!("[{events}]")
+}
+
+pub fn format_summary(timings: &[Timing], count: usize) -> String {
+    let mut sorted = timings.iter().collect::<Vec<_>>();
+    sorted.sort_by(|one, other| other.duration.cmp(&one.duration));
+
+    sorted
+        .into_iter()
+        .take(count)
+        .map(|timing| format!("{:>8.3}s  {}", timing.duration.as_secs_f64(), timing.name))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Chrome's trace format keys tracks by a numeric process ID, so pools are
+// mapped onto a pid to keep each pool's jobs visually grouped; the actual
+// concurrency lane within that pool is carried separately as `tid`.
+fn pool_pid(pool: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    pool.hash(&mut hasher);
+    hasher.finish() % 1024
+}
+
+// Rust's `Debug` escapes control characters as `\u{7}`-style braced,
+// variable-width sequences, which is not valid JSON, so escape by hand
+// instead.
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+
+    for character in value.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            character if (character as u32) < 0x20 => {
+                escaped.push_str(&format!("\\u{:04x}", character as u32))
+            }
+            character => escaped.push(character),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(name: &str, pool: &str, slot: usize, seconds: u64) -> Timing {
+        Timing {
+            name: name.into(),
+            pool: pool.into(),
+            slot,
+            start: UNIX_EPOCH,
+            duration: Duration::from_secs(seconds),
+        }
+    }
+
+    #[test]
+    fn escapes_quotes_backslashes_and_control_characters_in_trace_names() {
+        let trace = format_trace(&[timing("foo\"bar\\baz\n", "", 0, 1)]);
+
+        assert!(trace.contains(r#""name":"foo\"bar\\baz\n""#));
+    }
+
+    #[test]
+    fn formats_one_valid_json_object_per_timing() {
+        let trace = format_trace(&[timing("a", "", 0, 1), timing("b", "console", 1, 2)]);
+
+        assert!(trace.starts_with('[') && trace.ends_with(']'));
+        assert_eq!(trace.matches("\"ph\":\"X\"").count(), 2);
+    }
+
+    #[test]
+    fn summary_lists_the_slowest_timing_first() {
+        let summary = format_summary(&[timing("fast", "", 0, 1), timing("slow", "", 0, 5)], 10);
+
+        assert!(summary.find("slow").unwrap() < summary.find("fast").unwrap());
+    }
+
+    #[test]
+    fn summary_truncates_to_the_requested_count() {
+        let timings = (0..5)
+            .map(|second| timing("target", "", 0, second))
+            .collect::<Vec<_>>();
+
+        assert_eq!(format_summary(&timings, 2).lines().count(), 2);
+    }
+}
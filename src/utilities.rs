@@ -0,0 +1,14 @@
+// Small filesystem helpers for the runner, which needs a file's full
+// contents, with any failure reported with the path attached.
+
+use crate::error::InfrastructureError;
+use std::path::Path;
+use tokio::fs::read_to_string;
+
+pub async fn read_file(path: impl AsRef<Path>) -> Result<String, InfrastructureError> {
+    let path = path.as_ref();
+
+    read_to_string(path)
+        .await
+        .map_err(|error| InfrastructureError::with_path(error, path))
+}
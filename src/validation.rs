@@ -0,0 +1,154 @@
+// Rejects a build graph containing a dependency cycle (`a` built from `b`
+// built from `a`) up front, instead of the runner recursing into one and
+// overflowing the stack.
+
+use crate::ir::{Build, Configuration};
+use std::{collections::HashMap, error, fmt, sync::Arc};
+
+#[derive(Debug)]
+pub enum ValidationError {
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Cycle(path) => write!(formatter, "dependency cycle: {}", path.join(" -> ")),
+        }
+    }
+}
+
+impl error::Error for ValidationError {}
+
+pub struct BuildGraph {
+    outputs: HashMap<String, Arc<Build>>,
+}
+
+impl BuildGraph {
+    pub fn new(outputs: &HashMap<String, Arc<Build>>) -> Result<Self, ValidationError> {
+        let graph = Self {
+            outputs: outputs.clone(),
+        };
+
+        graph.check_cycles()?;
+
+        Ok(graph)
+    }
+
+    // Merges a dynamic module's edges into the graph and re-validates it,
+    // so a dyndep file that introduces a cycle is caught the same way a
+    // cycle in the top-level build file is.
+    pub fn insert(&mut self, configuration: &Configuration) -> Result<(), ValidationError> {
+        for (output, build) in configuration.outputs() {
+            self.outputs.insert(output.clone(), build.clone());
+        }
+
+        self.check_cycles()
+    }
+
+    fn check_cycles(&self) -> Result<(), ValidationError> {
+        let mut state = HashMap::new();
+
+        for key in self.outputs.keys() {
+            self.visit(key, &mut state, &mut vec![])?;
+        }
+
+        Ok(())
+    }
+
+    fn visit<'a>(
+        &'a self,
+        key: &'a str,
+        state: &mut HashMap<&'a str, bool>,
+        stack: &mut Vec<&'a str>,
+    ) -> Result<(), ValidationError> {
+        match state.get(key) {
+            Some(true) => return Ok(()),
+            Some(false) => {
+                let mut cycle = stack
+                    .iter()
+                    .skip_while(|&&visited| visited != key)
+                    .map(|visited| (*visited).to_string())
+                    .collect::<Vec<_>>();
+                cycle.push(key.into());
+
+                return Err(ValidationError::Cycle(cycle));
+            }
+            None => {}
+        }
+
+        state.insert(key, false);
+        stack.push(key);
+
+        if let Some(build) = self.outputs.get(key) {
+            for input in build.inputs().iter().chain(build.order_only_inputs()) {
+                if let Some((next_key, _)) = self.outputs.get_key_value(input.as_str()) {
+                    self.visit(next_key.as_str(), state, stack)?;
+                }
+            }
+        }
+
+        stack.pop();
+        state.insert(key, true);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{Build, Configuration};
+    use std::sync::Arc;
+
+    fn build(outputs: &[&str], inputs: &[&str]) -> Arc<Build> {
+        Arc::new(Build::new(
+            outputs.iter().map(|s| s.to_string()).collect(),
+            vec![],
+            inputs.iter().map(|s| s.to_string()).collect(),
+            vec![],
+            None,
+            None,
+            None,
+            None,
+        ))
+    }
+
+    fn outputs(builds: &[Arc<Build>]) -> HashMap<String, Arc<Build>> {
+        builds
+            .iter()
+            .flat_map(|build| build.outputs().iter().map(move |output| (output.clone(), build.clone())))
+            .collect()
+    }
+
+    #[test]
+    fn accepts_an_acyclic_graph() {
+        let a = build(&["a"], &["b"]);
+        let b = build(&["b"], &[]);
+
+        assert!(BuildGraph::new(&outputs(&[a, b])).is_ok());
+    }
+
+    #[test]
+    fn rejects_a_direct_cycle() {
+        let a = build(&["a"], &["b"]);
+        let b = build(&["b"], &["a"]);
+
+        assert!(matches!(
+            BuildGraph::new(&outputs(&[a, b])),
+            Err(ValidationError::Cycle(_))
+        ));
+    }
+
+    #[test]
+    fn insert_rejects_a_cycle_introduced_by_a_dynamic_module() {
+        let a = build(&["a"], &[]);
+        let mut graph = BuildGraph::new(&outputs(&[a])).unwrap();
+        let cyclic_a = build(&["a"], &["a"]);
+
+        assert!(matches!(
+            graph.insert(&Configuration::new(outputs(&[cyclic_a]), vec![], HashMap::new())),
+            Err(ValidationError::Cycle(_))
+        ));
+    }
+}